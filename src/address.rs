@@ -0,0 +1,282 @@
+//! RFC 5322 `From`-header parsing.
+//!
+//! The previous `[\w\.-]+@[\w\.-]+` regex mangled quoted display names,
+//! comments, and grouped/multiple addresses. This module pulls each
+//! address out properly, decoding RFC 2047 encoded-words (`=?UTF-8?B?...?=`)
+//! in display names along the way.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use regex::Regex;
+
+/// A single parsed address, e.g. `Jane Doe <jane@x.com>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub display_name: Option<String>,
+    pub local: String,
+    pub domain: String,
+}
+
+impl Address {
+    /// The canonical `local@domain` form, lowercased for aggregation.
+    pub fn email(&self) -> String {
+        format!("{}@{}", self.local, self.domain).to_lowercase()
+    }
+}
+
+/// Parses every address named by a `From`/`To`/`Cc`-style header value,
+/// including `Group Name: a@b.com, c@d.com;` group syntax.
+pub fn parse_addresses(raw: &str) -> Vec<Address> {
+    let no_comments = strip_comments(raw);
+    let trimmed = no_comments.trim();
+
+    let body = group_body(trimmed).unwrap_or(trimmed);
+
+    split_top_level(body, ',')
+        .into_iter()
+        .filter_map(|part| parse_single(&part))
+        .collect()
+}
+
+/// Convenience wrapper for headers that should only ever carry one address
+/// (e.g. `From`), returning the first one successfully parsed.
+pub fn parse_first_address(raw: &str) -> Option<Address> {
+    parse_addresses(raw).into_iter().next()
+}
+
+fn parse_single(token: &str) -> Option<Address> {
+    let token = token.trim();
+
+    if let (Some(start), Some(end)) = (token.find('<'), token.rfind('>')) {
+        if end > start {
+            let display_raw = token[..start].trim().trim_matches('"').trim();
+            let display_name = if display_raw.is_empty() {
+                None
+            } else {
+                Some(decode_encoded_words(display_raw))
+            };
+            let (local, domain) = split_local_domain(&token[start + 1..end])?;
+            return Some(Address { display_name, local, domain });
+        }
+    }
+
+    let (local, domain) = split_local_domain(token)?;
+    Some(Address { display_name: None, local, domain })
+}
+
+fn split_local_domain(addr: &str) -> Option<(String, String)> {
+    let addr = addr.trim().trim_matches('"');
+    let at = addr.rfind('@')?;
+    let local = addr[..at].trim().to_string();
+    let domain = addr[at + 1..].trim().to_string();
+    if local.is_empty() || domain.is_empty() {
+        return None;
+    }
+    Some((local, domain))
+}
+
+/// If `s` is `"Group Name: member, member;"`, returns the part between the
+/// top-level colon and the trailing semicolon.
+fn group_body(s: &str) -> Option<&str> {
+    if !s.ends_with(';') {
+        return None;
+    }
+    let colon = *top_level_indices(s, &[':']).first()?;
+    Some(s[colon + 1..s.len() - 1].trim())
+}
+
+/// Splits on `sep` occurrences that are outside quoted strings and angle
+/// brackets, so commas inside a quoted display name or an address don't
+/// split an entry in two.
+fn split_top_level(s: &str, sep: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for idx in top_level_indices(s, &[sep]) {
+        parts.push(s[start..idx].to_string());
+        start = idx + sep.len_utf8();
+    }
+    parts.push(s[start..].to_string());
+
+    parts
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Byte indices of any of `targets` that appear outside a quoted string and
+/// outside `<...>`.
+fn top_level_indices(s: &str, targets: &[char]) -> Vec<usize> {
+    let mut indices = Vec::new();
+    let mut in_quotes = false;
+    let mut angle_depth = 0i32;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        if in_quotes {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_quotes = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            '<' => angle_depth += 1,
+            '>' => angle_depth = (angle_depth - 1).max(0),
+            _ if angle_depth == 0 && targets.contains(&c) => indices.push(i),
+            _ => {}
+        }
+    }
+
+    indices
+}
+
+/// Drops parenthesised RFC 5322 comments that sit outside quoted strings.
+fn strip_comments(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut depth = 0u32;
+    let mut in_quotes = false;
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            out.push(c);
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            } else if c == '"' {
+                in_quotes = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' if depth == 0 => {
+                in_quotes = true;
+                out.push(c);
+            }
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+
+    out
+}
+
+/// Decodes RFC 2047 encoded-words (`=?charset?B?base64?=` / `=?charset?Q?text?=`)
+/// that appear in a display name. Unsupported charsets are decoded as UTF-8.
+fn decode_encoded_words(s: &str) -> String {
+    let re = Regex::new(r#"=\?[^?]+\?([bBqQ])\?([^?]*)\?="#).unwrap();
+
+    re.replace_all(s, |caps: &regex::Captures| {
+        let text = &caps[2];
+        match caps[1].to_ascii_lowercase().as_str() {
+            "b" => STANDARD
+                .decode(text)
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_else(|_| text.to_string()),
+            "q" => decode_quoted_printable_word(text),
+            _ => text.to_string(),
+        }
+    })
+    .into_owned()
+}
+
+fn decode_quoted_printable_word(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'_' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b'=' if i + 2 < bytes.len() => {
+                // Work on raw bytes rather than `text[i+1..i+3]`: the two
+                // bytes after `=` can land inside a multi-byte UTF-8
+                // codepoint when the input isn't valid quoted-printable,
+                // and str-slicing there panics on the char boundary.
+                let hex = &bytes[i + 1..i + 3];
+                if hex.iter().all(u8::is_ascii_hexdigit) {
+                    let byte = u8::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).unwrap();
+                    out.push(byte);
+                    i += 3;
+                } else {
+                    out.push(bytes[i]);
+                    i += 1;
+                }
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_display_name_and_address() {
+        let addrs = parse_addresses("Jane Doe <jane@x.com>");
+        assert_eq!(addrs.len(), 1);
+        assert_eq!(addrs[0].display_name.as_deref(), Some("Jane Doe"));
+        assert_eq!(addrs[0].email(), "jane@x.com");
+    }
+
+    #[test]
+    fn strips_parenthesised_comments() {
+        let addrs = parse_addresses("jane@x.com (Jane Doe)");
+        assert_eq!(addrs.len(), 1);
+        assert_eq!(addrs[0].email(), "jane@x.com");
+    }
+
+    #[test]
+    fn keeps_comma_inside_quoted_display_name() {
+        let addrs = parse_addresses("\"Doe, Jane\" <jane@x.com>, \"Roe, John\" <john@y.com>");
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(addrs[0].display_name.as_deref(), Some("Doe, Jane"));
+        assert_eq!(addrs[1].email(), "john@y.com");
+    }
+
+    #[test]
+    fn parses_group_syntax() {
+        let addrs = parse_addresses("Undisclosed: a@b.com, c@d.com;");
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(addrs[0].email(), "a@b.com");
+        assert_eq!(addrs[1].email(), "c@d.com");
+    }
+
+    #[test]
+    fn decodes_base64_encoded_word() {
+        // "Jane" base64-encoded.
+        let addrs = parse_addresses("=?UTF-8?B?SmFuZQ==?= <jane@x.com>");
+        assert_eq!(addrs[0].display_name.as_deref(), Some("Jane"));
+    }
+
+    #[test]
+    fn decodes_quoted_printable_encoded_word() {
+        let addrs = parse_addresses("=?UTF-8?Q?Jane=20Doe?= <jane@x.com>");
+        assert_eq!(addrs[0].display_name.as_deref(), Some("Jane Doe"));
+    }
+
+    #[test]
+    fn malformed_q_word_with_multibyte_char_does_not_panic() {
+        // A raw (non-encoded-word) "=" right before a multi-byte UTF-8
+        // character used to byte-slice mid-codepoint and panic.
+        assert_eq!(decode_quoted_printable_word("foo=€ bar"), "foo=€ bar");
+    }
+}
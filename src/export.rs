@@ -0,0 +1,60 @@
+//! Sender-report exporters: CSV, JSON, and newline-delimited JSON.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+
+#[derive(Serialize)]
+pub struct SenderRecord {
+    pub sender: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub count: usize,
+}
+
+pub trait Exporter {
+    fn export(&self, records: &[SenderRecord], path: &str) -> Result<()>;
+}
+
+pub struct CsvExporter;
+
+impl Exporter for CsvExporter {
+    fn export(&self, records: &[SenderRecord], path: &str) -> Result<()> {
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(&["Sender", "DisplayName", "MessageCount"])?;
+
+        for r in records {
+            wtr.write_record(&[
+                &r.sender,
+                r.display_name.as_deref().unwrap_or(""),
+                &r.count.to_string(),
+            ])?;
+        }
+
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+pub struct JsonExporter;
+
+impl Exporter for JsonExporter {
+    fn export(&self, records: &[SenderRecord], path: &str) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, records)?;
+        Ok(())
+    }
+}
+
+pub struct NdjsonExporter;
+
+impl Exporter for NdjsonExporter {
+    fn export(&self, records: &[SenderRecord], path: &str) -> Result<()> {
+        let mut file = File::create(path)?;
+        for r in records {
+            writeln!(file, "{}", serde_json::to_string(r)?)?;
+        }
+        Ok(())
+    }
+}
@@ -1,47 +1,415 @@
-use anyhow::Result;
+mod address;
+mod export;
+mod notify;
+
+use address::Address;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use clap::{Parser, ValueEnum};
 use csv::Writer;
+use export::{CsvExporter, Exporter, JsonExporter, NdjsonExporter, SenderRecord};
 use futures::{stream, StreamExt};
 use google_gmail1::{
-    api::MessagePartHeader,
-    Gmail,
+    api::{Message, MessagePart, MessagePartHeader},
+    Error as GmailError, Gmail,
 };
 use hyper_rustls;
 use yup_oauth2 as oauth2;
 use indicatif::{ProgressBar, ProgressStyle};
-use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tokio::time::{sleep, Duration};
 
 const BATCH_SIZE: usize = 100;
 const MAX_PARALLEL_BATCHES: usize = 5;      // Safe for Gmail API
 const DELAY_MS_BETWEEN_BATCHES: u64 = 80;   // Avoids rate-limit
+const GMAIL_BATCH_ENDPOINT: &str = "https://www.googleapis.com/batch/gmail/v1";
+const BATCH_BOUNDARY: &str = "gmail_extractor_batch_boundary";
+const CACHE_PATH: &str = "gmail_cache.json";
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // === Automatic OAuth, refresh tokens saved to disk ===
-    let secret = oauth2::read_application_secret("credentials.json").await?;
-    let auth = oauth2::InstalledFlowAuthenticator::builder(
-        secret,
-        oauth2::InstalledFlowReturnMethod::Interactive,
+pub(crate) const GMAIL_READONLY_SCOPE: &str = "https://www.googleapis.com/auth/gmail.readonly";
+pub(crate) const GMAIL_SEND_SCOPE: &str = "https://www.googleapis.com/auth/gmail.send";
+
+pub(crate) type Connector = hyper_rustls::HttpsConnector<hyper::client::HttpConnector>;
+pub(crate) type HttpClient = hyper::Client<Connector>;
+pub(crate) type Auth = oauth2::authenticator::Authenticator<Connector>;
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl Format {
+    fn default_output_path(self) -> &'static str {
+        match self {
+            Format::Csv => "gmail_senders_report.csv",
+            Format::Json => "gmail_senders_report.json",
+            Format::Ndjson => "gmail_senders_report.ndjson",
+        }
+    }
+
+    fn exporter(self) -> Box<dyn Exporter> {
+        match self {
+            Format::Csv => Box::new(CsvExporter),
+            Format::Json => Box::new(JsonExporter),
+            Format::Ndjson => Box::new(NdjsonExporter),
+        }
+    }
+}
+
+/// Gmail sender-analysis and export utility.
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Cli {
+    /// Gmail search query, e.g. `from:foo after:2023/01/01 has:attachment`
+    #[arg(short, long)]
+    query: Option<String>,
+
+    /// Extract attachments into this directory instead of counting senders
+    #[arg(long, value_name = "DIR")]
+    attachments: Option<String>,
+
+    /// Output format for the sender report
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+
+    /// Output path for the sender report (defaults to gmail_senders_report.<ext>)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Keep only the N highest senders
+    #[arg(long)]
+    top: Option<usize>,
+
+    /// Path to the OAuth client credentials JSON
+    #[arg(long, default_value = "credentials.json")]
+    credentials: String,
+
+    /// Path to the cached OAuth token
+    #[arg(long, default_value = "token.json")]
+    token: String,
+
+    /// How many message IDs to pack into one HTTP batch request
+    #[arg(long, default_value_t = BATCH_SIZE)]
+    batch_size: usize,
+
+    /// How many batch requests to run concurrently
+    #[arg(long, default_value_t = MAX_PARALLEL_BATCHES)]
+    max_parallel_batches: usize,
+
+    /// Delay between batches, to stay under Gmail's rate limits
+    #[arg(long, default_value_t = DELAY_MS_BETWEEN_BATCHES)]
+    delay_ms_between_batches: u64,
+
+    /// Path to the notifier config (webhook/email targets fired after a run)
+    #[arg(long, default_value = "notifiers.json")]
+    notify_config: String,
+}
+
+fn from_header_value(message: &Message) -> Option<&str> {
+    let headers = message.payload.as_ref()?.headers.as_ref()?;
+    for MessagePartHeader { name, value } in headers {
+        if name.as_deref().unwrap_or("").eq_ignore_ascii_case("from") {
+            return value.as_deref();
+        }
+    }
+    None
+}
+
+fn sender_from_message(message: &Message) -> Option<Address> {
+    address::parse_first_address(from_header_value(message)?)
+}
+
+/// Running tally for one sender address: how many messages, and the
+/// display name it was first seen under (headers don't always carry one).
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+struct SenderInfo {
+    display_name: Option<String>,
+    count: usize,
+}
+
+fn record_sender(counts: &mut HashMap<String, SenderInfo>, addr: &Address) {
+    let entry = counts.entry(addr.email()).or_default();
+    entry.count += 1;
+    if entry.display_name.is_none() {
+        entry.display_name = addr.display_name.clone();
+    }
+}
+
+/// Walks a message payload depth-first, collecting every part that carries
+/// a downloadable attachment (i.e. has both a `filename` and an `attachment_id`).
+fn collect_attachment_parts<'a>(part: &'a MessagePart, out: &mut Vec<&'a MessagePart>) {
+    let has_attachment = part
+        .filename
+        .as_deref()
+        .map(|f| !f.is_empty())
+        .unwrap_or(false)
+        && part
+            .body
+            .as_ref()
+            .and_then(|b| b.attachment_id.as_ref())
+            .is_some();
+
+    if has_attachment {
+        out.push(part);
+    }
+
+    if let Some(children) = &part.parts {
+        for child in children {
+            collect_attachment_parts(child, out);
+        }
+    }
+}
+
+fn sanitize_path_component(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | '@') { c } else { '_' })
+        .collect()
+}
+
+/// Builds one `multipart/mixed` sub-request for a metadata GET on `id`.
+fn build_batch_part(id: &str) -> String {
+    format!(
+        "--{boundary}\r\n\
+         Content-Type: application/http\r\n\
+         Content-ID: <{id}>\r\n\
+         \r\n\
+         GET /gmail/v1/users/me/messages/{id}?format=metadata&metadataHeaders=From HTTP/1.1\r\n\
+         \r\n",
+        boundary = BATCH_BOUNDARY,
+        id = id,
     )
-    .persist_tokens_to_disk("token.json")
-    .build()
-    .await?;
+}
 
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_native_roots().unwrap()
-        .https_or_http()
-        .enable_http1()
-        .build();
+/// Pulls the `boundary=...` parameter out of a `Content-Type` header value.
+fn extract_boundary(content_type: &str) -> Option<&str> {
+    content_type
+        .split(';')
+        .map(str::trim)
+        .find_map(|p| p.strip_prefix("boundary="))
+        .map(|b| b.trim_matches('"'))
+}
 
-    let client = hyper::Client::builder().build(https);
+/// Parses the `From` header out of a single JSON-encoded Gmail message body.
+fn parse_from_header(body: &str) -> Option<String> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    let headers = json.get("payload")?.get("headers")?.as_array()?;
+    headers
+        .iter()
+        .find(|h| h.get("name").and_then(|n| n.as_str()) == Some("From"))
+        .and_then(|h| h.get("value").and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+}
 
-    let hub = Gmail::new(client, auth);
+/// Pulls the request id back out of a batch sub-response's own `Content-ID`
+/// header, e.g. `Content-ID: <response-abc123>` -> `abc123`. The batch API
+/// does not guarantee sub-responses come back in request order, so this is
+/// the only reliable way to attribute a part to the message id that asked
+/// for it.
+fn extract_content_id(part: &str) -> Option<&str> {
+    let line = part.lines().find(|l| l.starts_with("Content-ID:"))?;
+    let start = line.find('<')? + 1;
+    let end = line.rfind('>')?;
+    line[start..end].strip_prefix("response-").or(Some(&line[start..end]))
+}
+
+/// Sends one metadata GET per entry of `ids` as a single `multipart/mixed`
+/// HTTP batch request, returning `(message_id, from_header)` for each requested id
+/// in the original order. Sub-responses are matched back to `ids` by their
+/// own `Content-ID` header, since the batch API doesn't guarantee the reply
+/// order matches the request order. Any sub-response whose status isn't 200,
+/// or that's missing from the reply entirely, is logged as a warning and
+/// yields `None` for the `From` header, mirroring the per-message error
+/// reporting the old one-request-per-message loop used to do.
+async fn fetch_metadata_batch(
+    client: &HttpClient,
+    auth: &Auth,
+    ids: &[String],
+) -> Result<Vec<(String, Option<String>)>> {
+    let token = auth.token(&[GMAIL_READONLY_SCOPE]).await?;
+    let bearer = token
+        .token()
+        .ok_or_else(|| anyhow!("OAuth token had no access token string"))?;
+
+    let mut body = String::new();
+    for id in ids {
+        body.push_str(&build_batch_part(id));
+    }
+    body.push_str(&format!("--{}--\r\n", BATCH_BOUNDARY));
+
+    let request = hyper::Request::builder()
+        .method(hyper::Method::POST)
+        .uri(GMAIL_BATCH_ENDPOINT)
+        .header("Authorization", format!("Bearer {}", bearer))
+        .header(
+            "Content-Type",
+            format!("multipart/mixed; boundary={}", BATCH_BOUNDARY),
+        )
+        .body(hyper::Body::from(body))?;
+
+    let resp = client.request(request).await?;
+    let content_type = resp
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let response_boundary = extract_boundary(&content_type)
+        .ok_or_else(|| anyhow!("batch response missing multipart boundary"))?
+        .to_string();
+
+    let bytes = hyper::body::to_bytes(resp.into_body()).await?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    let mut by_id: HashMap<&str, Option<String>> = HashMap::with_capacity(ids.len());
+
+    for part in text
+        .split(&format!("--{}", response_boundary))
+        .filter(|p| p.contains("Content-ID"))
+    {
+        let Some(content_id) = extract_content_id(part) else {
+            eprintln!("⚠ Batch sub-response had no Content-ID, dropping it");
+            continue;
+        };
+        let Some(id) = ids.iter().find(|id| id.as_str() == content_id) else {
+            eprintln!("⚠ Batch sub-response Content-ID {} did not match any requested message", content_id);
+            continue;
+        };
+
+        let status_ok = part
+            .lines()
+            .find(|l| l.starts_with("HTTP/"))
+            .map(|l| l.contains(" 200 "))
+            .unwrap_or(false);
+
+        if !status_ok {
+            eprintln!("⚠ Batch sub-request for message {} did not return 200", id);
+            by_id.insert(id.as_str(), None);
+            continue;
+        }
+
+        // The embedded HTTP response's JSON body starts after its own blank line.
+        let json_body = part.rsplit("\r\n\r\n").next().unwrap_or("").trim();
+        by_id.insert(id.as_str(), parse_from_header(json_body));
+    }
+
+    let results = ids
+        .iter()
+        .map(|id| {
+            let from_header = by_id.remove(id.as_str()).unwrap_or_else(|| {
+                eprintln!("⚠ Batch response for message {} was missing entirely", id);
+                None
+            });
+            (id.clone(), from_header)
+        })
+        .collect();
+
+    Ok(results)
+}
 
+/// Fetches one message in full, downloads every attachment it carries, and
+/// writes each one to `base_dir/<sender>/<message id>_<filename>`.
+async fn extract_attachments(
+    hub: &Gmail<Connector>,
+    msg_id: &str,
+    base_dir: &Path,
+) -> Result<usize> {
+    let (_, message) = hub
+        .users()
+        .messages_get("me", msg_id)
+        .format("full")
+        .add_metadata_headers("From")
+        .doit()
+        .await?;
+
+    let sender = sender_from_message(&message)
+        .map(|addr| addr.email())
+        .unwrap_or_else(|| "unknown_sender".to_string());
+    let dir = base_dir.join(sanitize_path_component(&sender));
+
+    let payload = match &message.payload {
+        Some(p) => p,
+        None => return Ok(0),
+    };
+
+    let mut parts = Vec::new();
+    collect_attachment_parts(payload, &mut parts);
+
+    if parts.is_empty() {
+        return Ok(0);
+    }
+
+    fs::create_dir_all(&dir)?;
+
+    let mut saved = 0;
+    for part in parts {
+        let filename = part.filename.as_deref().unwrap_or("attachment.bin");
+        let attachment_id = part
+            .body
+            .as_ref()
+            .and_then(|b| b.attachment_id.as_ref())
+            .expect("filtered above");
+
+        let (_, attachment) = hub
+            .users()
+            .messages_attachments_get("me", msg_id, attachment_id)
+            .doit()
+            .await?;
+
+        if let Some(data) = attachment.data {
+            // Gmail returns attachment bytes base64url-encoded, no padding.
+            let bytes = URL_SAFE_NO_PAD.decode(&data)?;
+            let path = dir.join(format!("{}_{}", msg_id, sanitize_path_component(filename)));
+            fs::write(&path, bytes)?;
+            saved += 1;
+        }
+    }
+
+    Ok(saved)
+}
+
+/// On-disk bookkeeping that makes repeated runs incremental: the last
+/// processed `historyId`, the running sender tallies, a message-id →
+/// sender map so a `messagesDeleted` history entry can be reversed, and the
+/// `--query` the tallies were scoped to (the History API has no query
+/// parameter, so a changed query invalidates incremental mode).
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    history_id: Option<String>,
+    query: Option<String>,
+    counts: HashMap<String, SenderInfo>,
+    senders: HashMap<String, String>,
+}
+
+fn load_cache(path: &str) -> Cache {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(path: &str, cache: &Cache) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(cache)?)?;
+    Ok(())
+}
+
+/// The mailbox's current `historyId`, used as the baseline for the next
+/// incremental run.
+async fn fetch_current_history_id(hub: &Gmail<Connector>) -> Result<Option<String>> {
+    let (_, profile) = hub.users().get_profile("me").doit().await?;
+    Ok(profile.history_id.map(|id| id.to_string()))
+}
+
+/// Pages through `users.messages.list` for every id matching `query`
+/// (`None` lists the whole mailbox), showing a spinner while the pages load.
+async fn list_message_ids(hub: &Gmail<Connector>, query: Option<&str>) -> Result<Vec<String>> {
     println!("📥 Fetching message IDs (all folders)…");
 
-    // === Get all message IDs ===
     let mut message_ids = Vec::new();
     let mut page_token: Option<String> = None;
 
@@ -56,6 +424,9 @@ async fn main() -> Result<()> {
 
     loop {
         let mut call = hub.users().messages_list("me");
+        if let Some(q) = query {
+            call = call.q(q);
+        }
         if let Some(ref token) = page_token {
             call = call.page_token(token);
         }
@@ -76,11 +447,28 @@ async fn main() -> Result<()> {
 
     pb_ids.finish_with_message(format!("✔ Found {} messages.", message_ids.len()));
 
-    // === Shared state ===
-    let counts = Arc::new(Mutex::new(HashMap::<String, usize>::new()));
-    let re = Regex::new(r"[\w\.-]+@[\w\.-]+").unwrap();
+    Ok(message_ids)
+}
+
+/// Lists every message matching `query`, fetches their `From` headers via
+/// batched HTTP requests, and returns the resulting sender tallies plus the
+/// message-id → sender map needed to reverse future deletions.
+async fn full_scan(
+    hub: &Gmail<Connector>,
+    raw_client: &HttpClient,
+    raw_auth: &Auth,
+    query: Option<&str>,
+    batch_size: usize,
+    max_parallel_batches: usize,
+    delay_ms_between_batches: u64,
+) -> Result<(HashMap<String, SenderInfo>, HashMap<String, String>)> {
+    let message_ids = list_message_ids(hub, query).await?;
+
+    let batches: Vec<Vec<String>> = message_ids
+        .chunks(batch_size)
+        .map(|chunk| chunk.iter().cloned().collect())
+        .collect();
 
-    // === Sender extraction progress bar ===
     let pb = ProgressBar::new(message_ids.len() as u64);
     pb.set_style(
         ProgressStyle::with_template(
@@ -89,79 +477,327 @@ async fn main() -> Result<()> {
         .progress_chars("##-"),
     );
 
-    // === Split message IDs into batches ===
-    let batches: Vec<Vec<String>> = message_ids
-        .chunks(BATCH_SIZE)
-        .map(|chunk| chunk.iter().cloned().collect())
-        .collect();
+    println!("🚀 Processing in {} batches ({} msgs each)…", batches.len(), batch_size);
 
-    println!("🚀 Processing in {} batches ({} msgs each)…",
-        batches.len(), BATCH_SIZE);
+    let counts = Arc::new(Mutex::new(HashMap::<String, SenderInfo>::new()));
+    let senders = Arc::new(Mutex::new(HashMap::<String, String>::new()));
 
     stream::iter(batches)
-        .for_each_concurrent(MAX_PARALLEL_BATCHES, |batch| {
-            let hub = &hub;
-            let re = &re;
+        .for_each_concurrent(max_parallel_batches, |batch| {
             let counts = Arc::clone(&counts);
+            let senders = Arc::clone(&senders);
             let pb = pb.clone();
 
             async move {
-                // === Fetch messages individually ===
-                for msg_id in &batch {
-                    match hub
-                        .users()
-                        .messages_get("me", msg_id)
-                        .format("metadata")
-                        .add_metadata_headers("From")
-                        .doit()
-                        .await
-                    {
-                        Ok((_, message)) => {
-                            if let Some(payload) = message.payload {
-                                if let Some(headers) = payload.headers {
-                                    for MessagePartHeader { name, value } in headers {
-                                        if name.as_deref().unwrap_or("").eq_ignore_ascii_case("from") {
-                                            if let Some(val) = value {
-                                                if let Some(mat) = re.find(&val) {
-                                                    let mut lock = counts.lock().unwrap();
-                                                    *lock.entry(mat.as_str().to_lowercase())
-                                                        .or_insert(0) += 1;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
+                match fetch_metadata_batch(raw_client, raw_auth, &batch).await {
+                    Ok(results) => {
+                        for (msg_id, from_header) in results {
+                            if let Some(addr) = from_header.as_deref().and_then(address::parse_first_address) {
+                                let email = addr.email();
+                                record_sender(&mut counts.lock().unwrap(), &addr);
+                                senders.lock().unwrap().insert(msg_id, email);
                             }
                         }
-                        Err(e) => {
-                            eprintln!("⚠ Failed to fetch message {}: {:?}", msg_id, e);
-                        }
+                    }
+                    Err(e) => {
+                        eprintln!("⚠ Batch request failed for {} messages: {:?}", batch.len(), e);
                     }
                 }
 
                 pb.inc(batch.len() as u64);
-                sleep(Duration::from_millis(DELAY_MS_BETWEEN_BATCHES)).await;
+                sleep(Duration::from_millis(delay_ms_between_batches)).await;
             }
         })
         .await;
 
     pb.finish_with_message("✔ Completed all batches.");
 
-    // === Save CSV ===
-    let counts = Arc::try_unwrap(counts)
-        .unwrap()
-        .into_inner()
-        .unwrap();
+    let counts = Arc::try_unwrap(counts).unwrap().into_inner().unwrap();
+    let senders = Arc::try_unwrap(senders).unwrap().into_inner().unwrap();
+
+    Ok((counts, senders))
+}
+
+/// Pages through `users.history.list` since `start_history_id`, applying
+/// `messagesAdded`/`messagesDeleted` entries directly to `counts`/`senders`.
+/// Returns `Ok(None)` when the API reports the start point is too old
+/// (expired history, surfaced as a 404) or when `start_history_id` isn't a
+/// valid `historyId` to begin with, signalling the caller should fall back
+/// to [`full_scan`] and rebuild the cache from scratch.
+async fn try_incremental_sync(
+    hub: &Gmail<Connector>,
+    start_history_id: &str,
+    counts: &mut HashMap<String, SenderInfo>,
+    senders: &mut HashMap<String, String>,
+) -> Result<Option<String>> {
+    let Ok(start_history_id) = start_history_id.parse::<u64>() else {
+        eprintln!("⚠ Cached historyId {:?} isn't a valid number", start_history_id);
+        return Ok(None);
+    };
+
+    let mut page_token: Option<String> = None;
+    let mut newest_history_id = start_history_id.to_string();
+
+    loop {
+        let mut call = hub.users().history_list("me").start_history_id(start_history_id);
+        if let Some(ref token) = page_token {
+            call = call.page_token(token);
+        }
+
+        let resp = match call.doit().await {
+            Ok((_, resp)) => resp,
+            Err(GmailError::Failure(resp)) if resp.status() == hyper::StatusCode::NOT_FOUND => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(id) = &resp.history_id {
+            newest_history_id = id.to_string();
+        }
+
+        for history in resp.history.unwrap_or_default() {
+            for added in history.messages_added.unwrap_or_default() {
+                let Some(msg_id) = added.message.and_then(|m| m.id) else { continue };
+                let (_, message) = hub
+                    .users()
+                    .messages_get("me", &msg_id)
+                    .format("metadata")
+                    .add_metadata_headers("From")
+                    .doit()
+                    .await?;
+                if let Some(addr) = sender_from_message(&message) {
+                    let email = addr.email();
+                    record_sender(counts, &addr);
+                    senders.insert(msg_id, email);
+                }
+            }
+
+            for deleted in history.messages_deleted.unwrap_or_default() {
+                let Some(msg_id) = deleted.message.and_then(|m| m.id) else { continue };
+                if let Some(email) = senders.remove(&msg_id) {
+                    if let Some(info) = counts.get_mut(&email) {
+                        info.count = info.count.saturating_sub(1);
+                        if info.count == 0 {
+                            counts.remove(&email);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(next) = resp.next_page_token {
+            page_token = Some(next);
+        } else {
+            break;
+        }
+    }
+
+    Ok(Some(newest_history_id))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // === Automatic OAuth, refresh tokens saved to disk ===
+    let secret = oauth2::read_application_secret(&cli.credentials).await?;
+    let auth = oauth2::InstalledFlowAuthenticator::builder(
+        secret,
+        oauth2::InstalledFlowReturnMethod::Interactive,
+    )
+    .persist_tokens_to_disk(&cli.token)
+    .build()
+    .await?;
+
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots().unwrap()
+        .https_or_http()
+        .enable_http1()
+        .build();
+
+    let client = hyper::Client::builder().build(https);
+    let raw_client = client.clone();
+    let raw_auth = auth.clone();
 
-    let mut wtr = Writer::from_path("gmail_senders_report.csv")?;
-    wtr.write_record(&["Sender", "MessageCount"])?;
+    let hub = Gmail::new(client, auth);
 
-    for (email, count) in counts {
-        wtr.write_record(&[email, count.to_string()])?;
+    // Load notifiers before doing any real work and, if an email notifier
+    // is configured, request `gmail.send` right away. That's the only new
+    // scope this tool ever needs beyond mail-reading, so asking for it up
+    // front means an otherwise-unattended run hits its one-time interactive
+    // consent prompt immediately at startup instead of after the report has
+    // already been written. Runs with no email notifier never request it.
+    let notifiers = notify::load_notifiers(&cli.notify_config, &raw_client, &raw_auth);
+    let extra_scopes: Vec<&str> = notifiers.iter().filter_map(|n| n.required_scope()).collect();
+    if !extra_scopes.is_empty() {
+        raw_auth.token(&extra_scopes).await?;
     }
 
-    wtr.flush()?;
+    if let Some(attachments_dir) = &cli.attachments {
+        let message_ids = list_message_ids(&hub, cli.query.as_deref()).await?;
+
+        let batches: Vec<Vec<String>> = message_ids
+            .chunks(cli.batch_size)
+            .map(|chunk| chunk.iter().cloned().collect())
+            .collect();
+
+        let base_dir = PathBuf::from(attachments_dir);
+        fs::create_dir_all(&base_dir)?;
+
+        let pb = ProgressBar::new(message_ids.len() as u64);
+        pb.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} messages"
+            )?
+            .progress_chars("##-"),
+        );
+
+        let saved_total = Arc::new(Mutex::new(0usize));
+
+        println!("📎 Extracting attachments into {}…", base_dir.display());
+
+        stream::iter(batches)
+            .for_each_concurrent(cli.max_parallel_batches, |batch| {
+                let hub = &hub;
+                let base_dir = &base_dir;
+                let saved_total = Arc::clone(&saved_total);
+                let pb = pb.clone();
+
+                async move {
+                    for msg_id in &batch {
+                        match extract_attachments(hub, msg_id, base_dir).await {
+                            Ok(n) => {
+                                if n > 0 {
+                                    *saved_total.lock().unwrap() += n;
+                                }
+                            }
+                            Err(e) => {
+                                eprintln!("⚠ Failed to extract attachments for {}: {:?}", msg_id, e);
+                            }
+                        }
+                    }
+
+                    pb.inc(batch.len() as u64);
+                    sleep(Duration::from_millis(cli.delay_ms_between_batches)).await;
+                }
+            })
+            .await;
+
+        pb.finish_with_message("✔ Completed all batches.");
+
+        println!(
+            "📁 Saved {} attachments under {}",
+            *saved_total.lock().unwrap(),
+            base_dir.display()
+        );
+
+        return Ok(());
+    }
+
+    // === Incremental sync, falling back to a full mailbox scan ===
+    let mut cache = load_cache(CACHE_PATH);
+
+    if cache.query != cli.query {
+        // The History API can't be scoped to a query, so a changed
+        // `--query` always forces a full rescan instead of silently
+        // drifting the cached tallies out from under the old scope.
+        if cache.history_id.is_some() {
+            println!("⚠ Query changed since the last run, rebuilding from a full scan…");
+        }
+        cache.history_id = None;
+    }
+
+    let rebuilt = match &cache.history_id {
+        Some(start_history_id) => {
+            println!("🔄 Syncing since historyId {start_history_id}…");
+            match try_incremental_sync(&hub, start_history_id, &mut cache.counts, &mut cache.senders).await {
+                Ok(Some(new_history_id)) => {
+                    cache.history_id = Some(new_history_id);
+                    false
+                }
+                Ok(None) => {
+                    println!("⚠ Cached historyId is too old, rebuilding from a full scan…");
+                    true
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        None => true,
+    };
+
+    if rebuilt {
+        let (counts, senders) = full_scan(
+            &hub,
+            &raw_client,
+            &raw_auth,
+            cli.query.as_deref(),
+            cli.batch_size,
+            cli.max_parallel_batches,
+            cli.delay_ms_between_batches,
+        )
+        .await?;
+        cache.counts = counts;
+        cache.senders = senders;
+        cache.history_id = fetch_current_history_id(&hub).await?;
+        cache.query = cli.query.clone();
+    }
+
+    save_cache(CACHE_PATH, &cache)?;
+
+    // === Save sender report, in whichever format was requested ===
+    let mut records: Vec<SenderRecord> = cache
+        .counts
+        .iter()
+        .map(|(email, info)| SenderRecord {
+            sender: email.clone(),
+            display_name: info.display_name.clone(),
+            count: info.count,
+        })
+        .collect();
+    records.sort_by(|a, b| b.count.cmp(&a.count));
+    if let Some(top) = cli.top {
+        records.truncate(top);
+    }
+
+    let output_path = cli
+        .output
+        .clone()
+        .unwrap_or_else(|| cli.format.default_output_path().to_string());
+    cli.format.exporter().export(&records, &output_path)?;
+
+    // === Save per-domain report ===
+    // Derived from the full `cache.counts`, not `records`, so it always
+    // covers every counted sender even when `--top` trims the sender report.
+    let mut domain_counts = HashMap::<String, usize>::new();
+    for (email, info) in &cache.counts {
+        let domain = email.rsplit('@').next().unwrap_or("unknown");
+        *domain_counts.entry(domain.to_string()).or_insert(0) += info.count;
+    }
+
+    let mut domain_wtr = Writer::from_path("gmail_domains_report.csv")?;
+    domain_wtr.write_record(&["Domain", "MessageCount"])?;
+
+    for (domain, count) in &domain_counts {
+        domain_wtr.write_record(&[domain, &count.to_string()])?;
+    }
+
+    domain_wtr.flush()?;
+
+    println!("📁 Saved {} and gmail_domains_report.csv", output_path);
+
+    // === Notify, if any notifiers are configured ===
+    if !notifiers.is_empty() {
+        let summary = notify::RunSummary {
+            total_messages: cache.counts.values().map(|info| info.count).sum(),
+            top_senders: records
+                .iter()
+                .take(10)
+                .map(|r| notify::TopSender { sender: r.sender.clone(), count: r.count })
+                .collect(),
+        };
+        notify::dispatch(&notifiers, &summary).await;
+    }
 
-    println!("📁 Saved gmail_senders_report.csv");
     Ok(())
 }